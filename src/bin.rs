@@ -1,4 +1,4 @@
-use mhz19::MHZ19;
+use mhz19::open;
 use std::time::Duration;
 use std::thread::sleep;
 use std::ffi::OsStr;
@@ -17,7 +17,7 @@ fn main() {
 }
 
 fn listen<T: AsRef<OsStr> + ?Sized>(port: &T) {
-    let mut mhz19 = MHZ19::open(port).unwrap();
+    let mut mhz19 = open(port).unwrap();
     loop {
         match { mhz19.read() } {
             Ok(value) => println!("{}", value),
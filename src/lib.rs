@@ -1,71 +1,225 @@
-use serial::SystemPort;
-use std::ffi::OsStr;
-use std::time::Duration;
-use std::io::{Write, Read};
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use core::time::Duration;
+use embedded_hal::delay::DelayNs;
+use embedded_hal_nb::serial::{Read as SerialRead, Write as SerialWrite};
 use err_derive::Error;
+use nb::block;
+
+#[cfg(feature = "std")]
+mod std_port;
+
+#[cfg(feature = "std")]
+pub use std_port::{open, OpenError, StdDelay, StdSerial};
 
+/// Error communicating with the mh-z19 over the serial port
 #[derive(Debug, Error)]
-pub enum Error {
-    #[error(display = "Error while opening serial port: {}", _0)]
-    Serial(#[error(cause)] serial::Error),
-    #[error(display = "Error communicating with serial port: {}", _0)]
-    IO(#[error(cause)] std::io::Error),
+pub enum Error<E: core::fmt::Debug> {
+    #[error(display = "Error communicating with serial port: {:?}", _0)]
+    Serial(E),
     #[error(display = "Invalid CRC value when reading for over 8 tries")]
     CRC,
+    #[error(display = "Timed out waiting for a response")]
+    Timeout,
 }
 
-impl From<serial::Error> for Error {
-    fn from(err: serial::Error) -> Self {
-        Error::Serial(err)
-    }
-}
-
-impl From<std::io::Error> for Error {
-    fn from(err: std::io::Error) -> Self {
-        Error::IO(err)
-    }
-}
-
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T, E> = core::result::Result<T, Error<E>>;
 
 /// mh-z19 CO₂ sensor
 ///
+/// Generic over the serial port and delay implementations so the driver can be used both on std
+/// platforms (see [`open`]) and on embedded targets through their `embedded-hal` implementations.
+///
 /// ## Usage
 ///
-/// ```
-/// use mhz19::MHZ19;
+/// ```no_run
+/// use mhz19::open;
 ///
-/// fn main() {
-///     let mut mhz19 = MHZ19::open("/dev/ttyUSB0").unwrap();
-///     println("CO₂ readout: {} ppm", mhz19.read().unwrap());
-/// }
+/// let mut mhz19 = open("/dev/ttyUSB0").unwrap();
+/// println!("CO₂ readout: {} ppm", mhz19.read().unwrap());
 /// ```
-pub struct MHZ19 {
-    port: SystemPort
+pub struct MHZ19<Serial, Delay> {
+    serial: Serial,
+    delay: Delay,
+    uptime_ms: u32,
+    range_ceiling: u16,
+    filter: Filter,
+    filtered_value: Option<i32>,
+    reject_invalid_during_warmup: bool,
+    pending_read: PendingRead,
+}
+
+enum PendingRead {
+    Idle,
+    Waiting {
+        buffer: [u8; 9],
+        filled: usize,
+        waited: Duration,
+    },
+}
+
+/// Configuration for [`read_with`](MHZ19::read_with), bounding how long a read may block and how
+/// many failed attempts (CRC failures or timeouts) it will tolerate before giving up
+#[derive(Debug, Clone, Copy)]
+pub struct ReadConfig {
+    /// Maximum time to wait for the first byte of a response
+    pub response_timeout: Duration,
+    /// Maximum number of retries after a CRC failure or a timeout before giving up with the last
+    /// attempt's error ([`Error::CRC`] or [`Error::Timeout`])
+    pub max_retries: u8,
+    /// Maximum time to wait between consecutive bytes of a response already in progress
+    pub inter_byte_timeout: Duration,
+}
+
+impl Default for ReadConfig {
+    fn default() -> Self {
+        ReadConfig {
+            response_timeout: Duration::from_millis(100),
+            max_retries: 8,
+            inter_byte_timeout: Duration::from_millis(100),
+        }
+    }
+}
+
+/// Granularity at which [`read_with`](MHZ19::read_with) polls the port while waiting for a response
+const POLL_INTERVAL_MS: u32 = 1;
+
+/// Smoothing strength applied to readings by [`read_filtered`](MHZ19::read_filtered)
+///
+/// MH-Z19 output is jittery during warm-up and under rapid CO₂ changes; each setting runs an
+/// exponential moving average over the raw samples, trading responsiveness for stability
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    /// Pass raw samples through unfiltered
+    Off,
+    Fast,
+    Medium,
+    Slow,
+}
+
+impl Filter {
+    fn k(self) -> i32 {
+        match self {
+            Filter::Off => 1,
+            Filter::Fast => 2,
+            Filter::Medium => 4,
+            Filter::Slow => 8,
+        }
+    }
+}
+
+/// A step change larger than this many ppm between samples resets the filter instead of being
+/// smoothed, since it likely reflects a genuine change rather than sensor noise
+const FILTER_RESET_THRESHOLD: i32 = 800;
+
+/// How long after opening the sensor is considered to still be warming up
+const WARMUP_MS: u32 = 3 * 60 * 1000;
+
+/// A CO₂ reading paired with the sensor's internal temperature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Measurement {
+    /// CO₂ concentration in ppm
+    pub co2: u16,
+    /// Internal sensor temperature in °C
+    pub temperature: i16,
+}
+
+/// A CO₂ reading paired with the sensor's internal temperature at its full fractional resolution,
+/// as returned by the "unlimited" command (see [`read_unclipped`](MHZ19::read_unclipped))
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnclippedMeasurement {
+    /// CO₂ concentration in ppm, not clipped to the configured range
+    pub co2: u16,
+    /// Internal sensor temperature in °C, at the sensor's full fractional resolution
+    ///
+    /// The fractional part is decoded best-effort from an undocumented response byte; see
+    /// [`read_unclipped`](MHZ19::read_unclipped)
+    pub temperature: f32,
 }
 
 /// Supported measure ranges
 pub enum Range {
     Range2000 = 2000,
     Range5000 = 5000,
+    /// Supported on newer MH-Z19B units
+    Range10000 = 10000,
 }
 
+#[derive(Clone, Copy)]
 enum Command {
     Read = 0x86,
     Zero = 0x87,
     Span = 0x88,
     ABC = 0x79,
-    Range = 0x99
+    Range = 0x99,
+    RecoveryReset = 0x78,
+    ABCStatus = 0x7d,
+    Version = 0xa0,
+    GetRange = 0x9b,
+    BackgroundCO2 = 0x9c,
+    Raw = 0x84,
+    Unclipped = 0x85,
 }
 
-const READ_WAIT: Duration = Duration::from_millis(100);
+const READ_WAIT_MS: u32 = 100;
 
-impl MHZ19 {
-    /// Connect to the mh-z19 at the specified serial port
-    pub fn open<T: AsRef<OsStr> + ?Sized>(port: &T) -> Result<Self> {
-        Ok(MHZ19 {
-            port: serial::open(port)?
-        })
+/// Round `numerator / denominator` to the nearest integer instead of truncating
+fn round_div(numerator: i32, denominator: i32) -> i32 {
+    if numerator >= 0 {
+        (numerator + denominator / 2) / denominator
+    } else {
+        -((-numerator + denominator / 2) / denominator)
+    }
+}
+
+/// Apply the exponential moving average [`read_filtered`](MHZ19::read_filtered) uses to smooth
+/// `sample` against `previous`, resetting instead of smoothing across a jump larger than
+/// [`FILTER_RESET_THRESHOLD`]
+fn apply_filter(filter: Filter, previous: Option<i32>, sample: i32) -> i32 {
+    match previous {
+        Some(v) if filter != Filter::Off && (sample - v).abs() <= FILTER_RESET_THRESHOLD => {
+            v + round_div(sample - v, filter.k())
+        }
+        _ => sample,
+    }
+}
+
+fn generate_command(command: Command, data1: u8, data2: u8) -> [u8; 9] {
+    let mut command = [0xff, 0x01, command as u8, data1, data2, 0x00, 0x00, 0x00, 0x00];
+    command[8] = crc8(&command);
+    command
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    let mut crc: u8 = 0;
+    for i in 1..8 {
+        crc = crc.wrapping_add(data[i]);
+    }
+    crc = !crc;
+    crc.wrapping_add(1)
+}
+
+impl<Serial, Delay, SerialError> MHZ19<Serial, Delay>
+where
+    Serial: SerialRead<u8, Error = SerialError> + SerialWrite<u8, Error = SerialError>,
+    Delay: DelayNs,
+    SerialError: core::fmt::Debug,
+{
+    /// Wrap an already open serial port and delay source into a driver instance
+    ///
+    /// Use this constructor on embedded targets, providing the `embedded-hal` implementations for
+    /// your platform. On std targets prefer [`open`], which takes care of opening the port.
+    pub fn new(serial: Serial, delay: Delay) -> Self {
+        MHZ19 {
+            serial,
+            delay,
+            uptime_ms: 0,
+            range_ceiling: Range::Range2000 as u16,
+            filter: Filter::Off,
+            filtered_value: None,
+            reject_invalid_during_warmup: false,
+            pending_read: PendingRead::Idle,
+        }
     }
 
     /// Read the CO2 value from the meter as ppm
@@ -76,47 +230,194 @@ impl MHZ19 {
     /// during this the thread is blocked
     ///
     /// If the crc check of the response fails the method will retry up to 8 times
-    pub fn read(&mut self) -> Result<u16> {
-        let command = MHZ19::generate_command(Command::Read, 0, 0);
-        let mut buffer = [0; 9];
-        let mut crc_err_count = 0;
+    pub fn read(&mut self) -> Result<u16, SerialError> {
+        self.read_measurement().map(|measurement| measurement.co2)
+    }
+
+    /// Read the CO2 value and internal temperature from the meter
+    ///
+    /// ## Blocking
+    ///
+    /// This command will wait for 100ms between sending the read command and getting the response
+    /// during this the thread is blocked
+    ///
+    /// If the crc check of the response fails the method will retry up to 8 times
+    pub fn read_measurement(&mut self) -> Result<Measurement, SerialError> {
+        let buffer = self.request(Command::Read, 0, 0)?;
+        Ok(Measurement {
+            co2: u16::from_be_bytes([buffer[2], buffer[3]]),
+            temperature: buffer[4] as i16 - 40,
+        })
+    }
+
+    /// Read the CO2 value as ppm, bounding the total wait time and retry budget via `cfg` instead
+    /// of the fixed 100ms wait and 8-retry budget of [`read`](MHZ19::read)
+    pub fn read_with(&mut self, cfg: &ReadConfig) -> Result<u16, SerialError> {
+        let mut retries = 0;
 
         loop {
-            self.port.write(&command)?;
-            std::thread::sleep(READ_WAIT);
-            self.port.read(&mut buffer)?;
-            let crc = MHZ19::crc8(&buffer);
-            if crc != buffer[8] {
-                crc_err_count += 1;
-                // flush
-                let _ = self.port.read(&mut buffer);
-                if crc_err_count > 8 {
-                    return Err(Error::CRC);
+            let command = generate_command(Command::Read, 0, 0);
+            self.write_command(&command)?;
+            let mut buffer = [0; 9];
+            let err = match self.read_frame_timeout(&mut buffer, cfg) {
+                Ok(()) => {
+                    let crc = crc8(&buffer);
+                    if crc == buffer[8] && buffer[0] == 0xff && buffer[1] == 0x86 {
+                        return Ok(u16::from_be_bytes([buffer[2], buffer[3]]));
+                    }
+                    Error::CRC
                 }
-            } else {
-                crc_err_count = 0;
-                if buffer[0] == 0xff && buffer[1] == 0x86 {
-                    return Ok(u16::from_be_bytes([buffer[2], buffer[3]]));
+                Err(Error::Timeout) => Error::Timeout,
+                Err(err) => return Err(err),
+            };
+
+            retries += 1;
+            if retries > cfg.max_retries {
+                return Err(err);
+            }
+        }
+    }
+
+    /// Issue the read command and poll the port for its response without waiting for the whole
+    /// frame: while no byte is available, each call blocks for a single `POLL_INTERVAL_MS` (1ms)
+    /// delay and then returns
+    ///
+    /// Returns `Ok(None)` if a complete frame hasn't arrived yet; call this again (e.g. from an
+    /// event loop) to continue waiting for it. The in-progress response, and the time already
+    /// spent waiting on it, are carried between calls, so retrying does not re-issue the command
+    /// until the pending one is resolved. Once the elapsed wait exceeds `cfg.response_timeout`
+    /// (for the first byte) or `cfg.inter_byte_timeout` (for the rest), this gives up and returns
+    /// [`Error::Timeout`] instead of `Ok(None)` forever, so a stuck read is always observable
+    ///
+    /// Note: unlike [`read`](MHZ19::read)/[`read_with`](MHZ19::read_with)/[`read_filtered`](MHZ19::read_filtered),
+    /// this method only advances the warm-up clock [`set_reject_invalid_during_warmup`](MHZ19::set_reject_invalid_during_warmup)
+    /// relies on while it is actually waiting on a pending response, not between separate calls
+    pub fn try_read(&mut self, cfg: &ReadConfig) -> Result<Option<u16>, SerialError> {
+        let (mut buffer, mut filled, mut waited) = match core::mem::replace(&mut self.pending_read, PendingRead::Idle)
+        {
+            PendingRead::Idle => {
+                let command = generate_command(Command::Read, 0, 0);
+                self.write_command(&command)?;
+                ([0u8; 9], 0, Duration::from_millis(0))
+            }
+            PendingRead::Waiting { buffer, filled, waited } => (buffer, filled, waited),
+        };
+
+        while filled < buffer.len() {
+            match self.serial.read() {
+                Ok(byte) => {
+                    buffer[filled] = byte;
+                    filled += 1;
+                    waited = Duration::from_millis(0);
+                }
+                Err(nb::Error::WouldBlock) => {
+                    let timeout = if filled == 0 {
+                        cfg.response_timeout
+                    } else {
+                        cfg.inter_byte_timeout
+                    };
+                    if waited >= timeout {
+                        return Err(Error::Timeout);
+                    }
+                    self.sleep(POLL_INTERVAL_MS);
+                    waited += Duration::from_millis(POLL_INTERVAL_MS as u64);
+                    self.pending_read = PendingRead::Waiting { buffer, filled, waited };
+                    return Ok(None);
                 }
+                Err(nb::Error::Other(e)) => return Err(Error::Serial(e)),
             }
-            std::thread::sleep(READ_WAIT);
         }
+
+        let crc = crc8(&buffer);
+        if crc != buffer[8] || buffer[0] != 0xff || buffer[1] != 0x86 {
+            return Err(Error::CRC);
+        }
+        Ok(Some(u16::from_be_bytes([buffer[2], buffer[3]])))
+    }
+
+    /// Read the raw, uncalibrated CO2 ADC value (cmd `0x84`)
+    ///
+    /// Unlike [`read`](MHZ19::read) this is not clipped to the configured range, which is useful
+    /// when doing your own calibration
+    pub fn read_raw(&mut self) -> Result<u16, SerialError> {
+        let buffer = self.request(Command::Raw, 0, 0)?;
+        Ok(u16::from_be_bytes([buffer[2], buffer[3]]))
+    }
+
+    /// Read the "unlimited" CO2 value and temperature (cmd `0x85`)
+    ///
+    /// Unlike [`read_measurement`](MHZ19::read_measurement) the CO2 value here is not clipped to
+    /// the configured range, and the temperature carries an extra fractional byte of resolution
+    /// instead of being truncated to whole degrees, which is useful when doing your own calibration
+    ///
+    /// The byte layout of this response is not documented in any datasheet we have access to; byte
+    /// 5 is assumed to be a hundredths-of-a-degree fraction added to the usual `byte[4] - 40`
+    /// whole-degree value, by analogy with how other MH-Z19 commands pack values. Treat the
+    /// fractional part of `temperature` as best-effort until it's been verified against real
+    /// hardware
+    pub fn read_unclipped(&mut self) -> Result<UnclippedMeasurement, SerialError> {
+        let buffer = self.request(Command::Unclipped, 0, 0)?;
+        let whole_degrees = buffer[4] as i16 - 40;
+        let fractional_degrees = buffer[5] as f32 / 100.0;
+        Ok(UnclippedMeasurement {
+            co2: u16::from_be_bytes([buffer[2], buffer[3]]),
+            temperature: whole_degrees as f32 + fractional_degrees,
+        })
+    }
+
+    /// Read the sensor's firmware version
+    pub fn firmware_version(&mut self) -> Result<[u8; 4], SerialError> {
+        let buffer = self.request(Command::Version, 0, 0)?;
+        let mut version = [0; 4];
+        version.copy_from_slice(&buffer[2..6]);
+        Ok(version)
+    }
+
+    /// Read back the detection range currently configured on the sensor
+    ///
+    /// Can be used to verify that a previous call to [`set_range`](MHZ19::set_range) took effect
+    pub fn get_range(&mut self) -> Result<u32, SerialError> {
+        let buffer = self.request(Command::GetRange, 0, 0)?;
+        Ok(u32::from_be_bytes([buffer[2], buffer[3], buffer[4], buffer[5]]))
+    }
+
+    /// Check whether automatic baseline correction is currently enabled
+    ///
+    /// Can be used to verify that a previous call to [`enable_abc`](MHZ19::enable_abc) took effect
+    pub fn abc_enabled(&mut self) -> Result<bool, SerialError> {
+        let buffer = self.request(Command::ABCStatus, 0, 0)?;
+        Ok(buffer[7] != 0)
+    }
+
+    /// Read the background CO2 value used as the baseline for automatic calibration
+    pub fn background_co2(&mut self) -> Result<u16, SerialError> {
+        let buffer = self.request(Command::BackgroundCO2, 0, 0)?;
+        Ok(u16::from_be_bytes([buffer[2], buffer[3]]))
+    }
+
+    /// Trigger the sensor's recovery reset
+    pub fn recovery_reset(&mut self) -> Result<(), SerialError> {
+        let command = generate_command(Command::RecoveryReset, 0, 0);
+        self.write_command(&command)?;
+        Ok(())
     }
 
     /// Tell the mh-z19 to zero-point calibrate
     ///
     /// Sensor should be at 400ppm when calibrating
-    pub fn zero_calibrate(&mut self) -> Result<()> {
-        self.port.write(&MHZ19::generate_command(Command::Zero, 0, 0))?;
+    pub fn zero_calibrate(&mut self) -> Result<(), SerialError> {
+        let command = generate_command(Command::Zero, 0, 0);
+        self.write_command(&command)?;
         Ok(())
     }
 
     /// Tell the mh-z19 to span-point calibrate
     ///
     /// Sensor should be at target level when calibrating
-    pub fn span_calibrate(&mut self, value: u16) -> Result<()> {
+    pub fn span_calibrate(&mut self, value: u16) -> Result<(), SerialError> {
         let value_bytes = value.to_be_bytes();
-        self.port.write(&MHZ19::generate_command(Command::Span, value_bytes[0], value_bytes[1]))?;
+        let command = generate_command(Command::Span, value_bytes[0], value_bytes[1]);
+        self.write_command(&command)?;
         Ok(())
     }
 
@@ -129,32 +430,189 @@ impl MHZ19 {
     /// multiple hours each day allowing the CO₂ values to come down to outside levels
     ///
     /// For units produced after 2015 this should be enabled by default
-    pub fn enable_abc(&mut self, enable: bool) -> Result<()> {
-        self.port.write(&MHZ19::generate_command(Command::ABC, if enable { 0xa0 } else { 0x00 }, 0))?;
+    pub fn enable_abc(&mut self, enable: bool) -> Result<(), SerialError> {
+        let command = generate_command(Command::ABC, if enable { 0xa0 } else { 0x00 }, 0);
+        self.write_command(&command)?;
         Ok(())
     }
 
     /// Set the detection range for the sensor
     ///
     /// A range of 2000ppm or 5000ppm is supported
-    pub fn set_range(&mut self, range: Range) -> Result<()> {
-        let value_bytes = (range as u16).to_be_bytes();
-        self.port.write(&MHZ19::generate_command(Command::Range, value_bytes[0], value_bytes[1]))?;
+    /// (10000ppm as well on newer MH-Z19B units)
+    pub fn set_range(&mut self, range: Range) -> Result<(), SerialError> {
+        self.range_ceiling = range as u16;
+        let value_bytes = self.range_ceiling.to_be_bytes();
+        let command = generate_command(Command::Range, value_bytes[0], value_bytes[1]);
+        self.write_command(&command)?;
+        Ok(())
+    }
+
+    /// Configure the smoothing filter applied by [`read_filtered`](MHZ19::read_filtered)
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.filter = filter;
+        self.filtered_value = None;
+    }
+
+    /// Enable or disable discarding obviously invalid samples (0ppm, or pinned at the configured
+    /// range ceiling) seen during the sensor's warm-up window, in [`read_filtered`](MHZ19::read_filtered)
+    ///
+    /// The warm-up window is tracked from time spent sleeping on the injected delay, so it only
+    /// advances while the driver waits on it: during reads and the other query commands (such as
+    /// `firmware_version` or `get_range`), not during the time between calls
+    pub fn set_reject_invalid_during_warmup(&mut self, reject: bool) {
+        self.reject_invalid_during_warmup = reject;
+    }
+
+    /// Read the CO2 value, smoothed according to the filter set with [`set_filter`](MHZ19::set_filter)
+    ///
+    /// If [`set_reject_invalid_during_warmup`](MHZ19::set_reject_invalid_during_warmup) is enabled,
+    /// samples of 0ppm or pinned at the configured range ceiling are discarded in favor of the last
+    /// known good value while the sensor is still within its warm-up window; if there is no such
+    /// value yet, the sample is returned unchanged
+    pub fn read_filtered(&mut self) -> Result<u16, SerialError> {
+        let sample = self.read()?;
+
+        if self.reject_invalid_during_warmup
+            && self.uptime_ms < WARMUP_MS
+            && (sample == 0 || sample >= self.range_ceiling)
+        {
+            return Ok(self.filtered_value.map(|v| v as u16).unwrap_or(sample));
+        }
+
+        let filtered = apply_filter(self.filter, self.filtered_value, sample as i32);
+        self.filtered_value = Some(filtered);
+        Ok(filtered as u16)
+    }
+
+    /// Send `command` and wait for its CRC-validated 9 byte response, retrying up to 8 times on
+    /// CRC failure
+    fn request(&mut self, command: Command, data1: u8, data2: u8) -> Result<[u8; 9], SerialError> {
+        let expected_reply = command as u8;
+        let command = generate_command(command, data1, data2);
+        let mut buffer = [0; 9];
+        let mut crc_err_count = 0;
+
+        loop {
+            self.write_command(&command)?;
+            self.sleep(READ_WAIT_MS);
+            self.read_frame(&mut buffer)?;
+            let crc = crc8(&buffer);
+            if crc != buffer[8] {
+                crc_err_count += 1;
+                // flush
+                let _ = self.read_frame(&mut buffer);
+                if crc_err_count > 8 {
+                    return Err(Error::CRC);
+                }
+            } else {
+                crc_err_count = 0;
+                if buffer[0] == 0xff && buffer[1] == expected_reply {
+                    return Ok(buffer);
+                }
+            }
+            self.sleep(READ_WAIT_MS);
+        }
+    }
+
+    /// Delay for `ms` milliseconds, tracking elapsed time since [`new`](MHZ19::new)/[`open`] so the
+    /// warm-up guard in [`read_filtered`](MHZ19::read_filtered) knows when the sensor has settled
+    fn sleep(&mut self, ms: u32) {
+        self.delay.delay_ms(ms);
+        self.uptime_ms = self.uptime_ms.saturating_add(ms);
+    }
+
+    fn write_command(&mut self, command: &[u8; 9]) -> Result<(), SerialError> {
+        for &byte in command {
+            block!(self.serial.write(byte)).map_err(Error::Serial)?;
+        }
         Ok(())
     }
 
-    fn generate_command(command: Command, data1: u8, data2: u8) -> [u8; 9] {
-        let mut command = [0xff, 0x01, command as u8, data1, data2, 0x00, 0x00, 0x00, 0x00];
-        command[8] = MHZ19::crc8(&command);
-        command
+    /// Read a 9 byte frame, polling the port with a short sleep between attempts instead of
+    /// busy-spinning while bytes are not yet available
+    fn read_frame(&mut self, buffer: &mut [u8; 9]) -> Result<(), SerialError> {
+        self.read_frame_timeout(buffer, &ReadConfig::default())
     }
 
-    fn crc8(data: &[u8]) -> u8 {
-        let mut crc: u8 = 0;
-        for i in 1..8 {
-            crc = crc.wrapping_add(data[i]);
+    /// Read a 9 byte frame, bailing out with [`Error::Timeout`] if no byte arrives within
+    /// `cfg.response_timeout` (for the first byte) or `cfg.inter_byte_timeout` (for the rest)
+    fn read_frame_timeout(&mut self, buffer: &mut [u8; 9], cfg: &ReadConfig) -> Result<(), SerialError> {
+        let mut filled = 0;
+        let mut waited = Duration::from_millis(0);
+
+        while filled < buffer.len() {
+            match self.serial.read() {
+                Ok(byte) => {
+                    buffer[filled] = byte;
+                    filled += 1;
+                    waited = Duration::from_millis(0);
+                }
+                Err(nb::Error::WouldBlock) => {
+                    let timeout = if filled == 0 {
+                        cfg.response_timeout
+                    } else {
+                        cfg.inter_byte_timeout
+                    };
+                    if waited >= timeout {
+                        return Err(Error::Timeout);
+                    }
+                    self.sleep(POLL_INTERVAL_MS);
+                    waited += Duration::from_millis(POLL_INTERVAL_MS as u64);
+                }
+                Err(nb::Error::Other(e)) => return Err(Error::Serial(e)),
+            }
         }
-        crc = !crc;
-        crc.wrapping_add(1)
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_div_rounds_to_nearest() {
+        assert_eq!(round_div(5, 2), 3);
+        assert_eq!(round_div(7, 2), 4);
+        assert_eq!(round_div(-5, 2), -3);
+        assert_eq!(round_div(-7, 2), -4);
+    }
+
+    #[test]
+    fn apply_filter_passes_through_when_off() {
+        assert_eq!(apply_filter(Filter::Off, Some(500), 600), 600);
+    }
+
+    #[test]
+    fn apply_filter_passes_through_first_sample() {
+        assert_eq!(apply_filter(Filter::Fast, None, 400), 400);
+    }
+
+    #[test]
+    fn apply_filter_smooths_towards_the_sample() {
+        assert_eq!(apply_filter(Filter::Fast, Some(400), 420), 410);
+        assert_eq!(apply_filter(Filter::Medium, Some(420), 400), 415);
+    }
+
+    #[test]
+    fn apply_filter_smooths_at_the_reset_boundary() {
+        assert_eq!(apply_filter(Filter::Fast, Some(400), 1200), 800);
+    }
+
+    #[test]
+    fn apply_filter_resets_past_the_boundary() {
+        assert_eq!(apply_filter(Filter::Slow, Some(400), 1300), 1300);
+    }
+
+    #[test]
+    fn generate_command_sets_the_matching_crc() {
+        let command = generate_command(Command::Read, 0, 0);
+        assert_eq!(command, [0xff, 0x01, 0x86, 0x00, 0x00, 0x00, 0x00, 0x00, 0x79]);
+    }
+
+    #[test]
+    fn crc8_matches_a_known_frame() {
+        assert_eq!(crc8(&[0xff, 0x01, 0x86, 0x00, 0x00, 0x00, 0x00, 0x00, 0x79]), 0x79);
     }
 }
@@ -0,0 +1,88 @@
+//! std-only convenience layer wiring a [`serial::SystemPort`] and `std::thread::sleep` up to the
+//! `embedded-hal` traits the generic [`MHZ19`](crate::MHZ19) driver expects.
+
+use crate::MHZ19;
+use embedded_hal::delay::DelayNs;
+use embedded_hal_nb::serial::{ErrorType, Read as SerialRead, Write as SerialWrite};
+use err_derive::Error;
+use serial::SerialPort;
+use std::ffi::OsStr;
+use std::io;
+use std::time::Duration;
+
+/// Port timeout used for non-blocking reads: a read call returns immediately (with a `TimedOut`
+/// error, translated to `nb::Error::WouldBlock`) instead of waiting for a byte that hasn't arrived
+const POLL_TIMEOUT: Duration = Duration::from_millis(0);
+
+/// Error opening the underlying serial port
+#[derive(Debug, Error)]
+pub enum OpenError {
+    #[error(display = "Error while opening serial port: {}", _0)]
+    Serial(#[error(cause)] serial::Error),
+}
+
+impl From<serial::Error> for OpenError {
+    fn from(err: serial::Error) -> Self {
+        OpenError::Serial(err)
+    }
+}
+
+/// `embedded-hal` serial port backed by a [`serial::SystemPort`], polled with a zero-length
+/// read timeout so [`read`](StdSerial::read) never blocks the thread
+pub struct StdSerial(serial::SystemPort);
+
+impl StdSerial {
+    fn open<T: AsRef<OsStr> + ?Sized>(port: &T) -> serial::Result<Self> {
+        let mut port = serial::open(port)?;
+        port.set_timeout(POLL_TIMEOUT)?;
+        Ok(StdSerial(port))
+    }
+}
+
+impl ErrorType for StdSerial {
+    type Error = io::Error;
+}
+
+impl SerialRead<u8> for StdSerial {
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        let mut byte = [0; 1];
+        match io::Read::read(&mut self.0, &mut byte) {
+            Ok(0) => Err(nb::Error::WouldBlock),
+            Ok(_) => Ok(byte[0]),
+            Err(err) if matches!(err.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock) => {
+                Err(nb::Error::WouldBlock)
+            }
+            Err(err) => Err(nb::Error::Other(err)),
+        }
+    }
+}
+
+impl SerialWrite<u8> for StdSerial {
+    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+        io::Write::write_all(&mut self.0, &[word])?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        io::Write::flush(&mut self.0)?;
+        Ok(())
+    }
+}
+
+/// `embedded-hal` delay source backed by `std::thread::sleep`
+pub struct StdDelay;
+
+impl DelayNs for StdDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        std::thread::sleep(Duration::from_nanos(ns as u64));
+    }
+}
+
+/// Connect to the mh-z19 at the specified serial port
+///
+/// This is a convenience constructor for std targets, wrapping a [`serial::SystemPort`] and a
+/// `std::thread::sleep`-backed delay into a [`MHZ19`] instance. On embedded targets construct the
+/// driver directly with [`MHZ19::new`], passing your platform's `embedded-hal` implementations.
+pub fn open<T: AsRef<OsStr> + ?Sized>(port: &T) -> Result<MHZ19<StdSerial, StdDelay>, OpenError> {
+    Ok(MHZ19::new(StdSerial::open(port)?, StdDelay))
+}